@@ -2,24 +2,128 @@ use pest::Parser;
 use pest_derive::Parser;
 use pest::iterators::Pair;
 use std::collections::HashMap;
+use std::collections::HashSet;
 use std::process::Command;
 
 #[derive(Parser)]
 #[grammar = "grammar.pest"]
 struct DSLParser;
 
+/// Named flag values bound to a command's declared `params`, e.g. `--name value`.
+pub type Flags = HashMap<String, String>;
+
+/// A line/column location plus the source line itself, so a diagnostic can
+/// render a caret pointing at the offending token.
+#[derive(Debug)]
+pub struct SourceSpan {
+    pub line: usize,
+    pub col: usize,
+    pub line_text: String,
+}
+
+/// A human-facing error with an optional source location to highlight.
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub message: String,
+    pub span: Option<SourceSpan>,
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "\x1b[31m[error]\x1b[0m {}", self.message)?;
+        if let Some(span) = &self.span {
+            writeln!(f, "  --> line {}:{}", span.line, span.col)?;
+            writeln!(f, "   |")?;
+            writeln!(f, "{:>3} | {}", span.line, span.line_text)?;
+            let caret = " ".repeat(span.col.saturating_sub(1)) + "^";
+            write!(f, "   | {}", caret)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum CommanderError {
+    Parse(Diagnostic),
+    UnknownCommand(Diagnostic),
+    UnknownVariable(Diagnostic),
+    /// A `depends` statement names a node that doesn't exist.
+    UnknownDependency(Diagnostic),
+    /// `topo_sort` found a back-edge; the diagnostic message carries the
+    /// full chain, e.g. "Dependency cycle detected: a -> b -> a".
+    Cycle(Diagnostic),
+    /// A node was invoked without a required `--flag` and had no fallback
+    /// value already set in the Environment.
+    MissingFlags(Diagnostic),
+    Io(String),
+}
+
+impl std::fmt::Display for CommanderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            CommanderError::Parse(d) => write!(f, "{}", d),
+            CommanderError::UnknownCommand(d) => write!(f, "{}", d),
+            CommanderError::UnknownVariable(d) => write!(f, "{}", d),
+            CommanderError::UnknownDependency(d) => write!(f, "{}", d),
+            CommanderError::Cycle(d) => write!(f, "{}", d),
+            CommanderError::MissingFlags(d) => write!(f, "{}", d),
+            CommanderError::Io(msg) => write!(f, "\x1b[31m[error]\x1b[0m {}", msg),
+        }
+    }
+}
+
+impl From<pest::error::Error<Rule>> for CommanderError {
+    fn from(err: pest::error::Error<Rule>) -> Self {
+        let (line, col) = match err.line_col {
+            pest::error::LineColLocation::Pos(pos) => pos,
+            pest::error::LineColLocation::Span(start, _) => start,
+        };
+        let line_text = err.line().to_string();
+        let message = err.variant.message().to_string();
+
+        CommanderError::Parse(Diagnostic {
+            message,
+            span: Some(SourceSpan { line, col, line_text }),
+        })
+    }
+}
+
 #[derive(Debug)]
 pub struct CommandNode {
     pub name: String,
-    pub params: Vec<String>,
+    pub doc: Option<String>,
+    pub params: Vec<Param>,
     pub statements: Vec<Statement>,
 }
 
+/// A declared `--flag` parameter, with its optional `: "description"` text
+/// for `--help` to print.
+#[derive(Debug)]
+pub struct Param {
+    pub name: String,
+    pub doc: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum AssignOp {
+    /// `name = value`: always overwrites.
+    Set,
+    /// `name ?= value`: only takes effect if `name` is currently unset.
+    Default,
+    /// `name += value`: concatenates onto the current value (empty if unset).
+    Append,
+}
+
 #[derive(Debug)]
 pub enum Statement {
     Command(CommandNode),
     Exec(String),
-    Assignment(String, String),
+    Assignment(String, AssignOp, String),
+    /// `name <op> $(cmd)`: runs `cmd` through the shell and stores its
+    /// trimmed stdout under `name` (exit code under `name.code`), honoring
+    /// `op` the same way `Assignment` does — `?=` skips the command
+    /// entirely if `name` is already set, `+=` concatenates onto it.
+    Capture(String, AssignOp, String),
     Depends(Vec<String>),
     If {
         condition: Condition,
@@ -43,36 +147,121 @@ pub struct Condition {
 #[derive(Debug, Clone)]
 pub struct Environment {
     vars: HashMap<String, String>,
+    /// When set, an unknown variable in `expand` is a fatal diagnostic
+    /// instead of silently expanding to an empty string.
+    strict: bool,
 }
 
 impl Environment {
-    fn new() -> Self {
-        Self { vars: HashMap::new() }
+    fn with_strict(strict: bool) -> Self {
+        Self { vars: HashMap::new(), strict }
     }
-    
+
     fn set(&mut self, key: String, value: String) {
         self.vars.insert(key, value);
     }
-    
-    fn expand(&self, text: &str) -> String {
-        let mut result = text.to_string();
-        for (key, value) in &self.vars {
-            result = result.replace(&format!("${}", key), value);
+
+    fn get(&self, key: &str) -> Option<&String> {
+        self.vars.get(key)
+    }
+
+    /// Resolves one `$name`/`${name}` reference into `out`, falling back to
+    /// `fallback` (from `${name:-fallback}`) or an empty string when unset.
+    /// In strict mode, an unset variable with no fallback is a hard error
+    /// instead of silently expanding to nothing.
+    fn resolve_var(
+        &self,
+        name: &str,
+        fallback: Option<&str>,
+        out: &mut String,
+    ) -> Result<(), CommanderError> {
+        match self.vars.get(name) {
+            Some(value) => out.push_str(value),
+            None => match fallback {
+                Some(f) => out.push_str(f),
+                None => {
+                    if self.strict {
+                        return Err(CommanderError::UnknownVariable(Diagnostic {
+                            message: format!("Unknown variable '{}'", name),
+                            span: None,
+                        }));
+                    }
+                }
+            },
         }
-        result
+        Ok(())
     }
-    
-    fn eval_condition(&self, cond: &Condition) -> bool {
-        let left = self.expand(&cond.left);
-        let right = self.expand(&cond.right);
+
+    /// Single left-to-right scan over `text` recognizing bare `$name`, the
+    /// delimited `${name}`, the default form `${name:-fallback}`, and a
+    /// literal `$$`. Each resolved value is emitted into the output buffer
+    /// exactly once, so substituted text is never re-scanned. In strict
+    /// mode, an unknown variable aborts the scan with `CommanderError`.
+    fn expand(&self, text: &str) -> Result<String, CommanderError> {
+        let chars: Vec<char> = text.chars().collect();
+        let mut out = String::with_capacity(text.len());
+        let mut i = 0;
+
+        while i < chars.len() {
+            let c = chars[i];
+
+            if c != '$' {
+                out.push(c);
+                i += 1;
+                continue;
+            }
+
+            if chars.get(i + 1) == Some(&'$') {
+                out.push('$');
+                i += 2;
+                continue;
+            }
+
+            if chars.get(i + 1) == Some(&'{') {
+                if let Some(end) = chars[i + 2..].iter().position(|&ch| ch == '}').map(|p| i + 2 + p) {
+                    let inner: String = chars[i + 2..end].iter().collect();
+                    let (name, fallback) = match inner.split_once(":-") {
+                        Some((n, f)) => (n, Some(f)),
+                        None => (inner.as_str(), None),
+                    };
+                    self.resolve_var(name, fallback, &mut out)?;
+                    i = end + 1;
+                    continue;
+                }
+                // Unterminated `${`: fall through and treat '$' literally.
+            }
+
+            let start = i + 1;
+            let mut end = start;
+            while end < chars.len() && (chars[end].is_ascii_alphanumeric() || chars[end] == '_') {
+                end += 1;
+            }
+
+            if end == start {
+                out.push(c);
+                i += 1;
+                continue;
+            }
+
+            let name: String = chars[start..end].iter().collect();
+            self.resolve_var(&name, None, &mut out)?;
+            i = end;
+        }
+
+        Ok(out)
+    }
+
+    fn eval_condition(&self, cond: &Condition) -> Result<bool, CommanderError> {
+        let left = self.expand(&cond.left)?;
+        let right = self.expand(&cond.right)?;
         
-        match cond.op.as_str() {
+        Ok(match cond.op.as_str() {
             "==" => left == right,
             "!=" => left != right,
             ">" => left > right,
             "<" => left < right,
             _ => false,
-        }
+        })
     }
 }
 
@@ -103,9 +292,19 @@ fn parse_statements(pairs: pest::iterators::Pairs<Rule>) -> Vec<Statement> {
             Rule::assignment => {
                 let mut parts = stmt.into_inner();
                 let var_name = parts.next().unwrap().as_str().to_string();
+                let op = match parts.next().unwrap().as_str() {
+                    "?=" => AssignOp::Default,
+                    "+=" => AssignOp::Append,
+                    _ => AssignOp::Set,
+                };
                 let value_pair = parts.next().unwrap();
-                let value = parse_value(value_pair);
-                statements.push(Statement::Assignment(var_name, value));
+                if value_pair.as_rule() == Rule::command_subst {
+                    let cmd = value_pair.into_inner().next().unwrap().as_str().to_string();
+                    statements.push(Statement::Capture(var_name, op, cmd));
+                } else {
+                    let value = parse_value(value_pair);
+                    statements.push(Statement::Assignment(var_name, op, value));
+                }
             }
             Rule::depends => {
                 let deps: Vec<String> = stmt.into_inner()
@@ -160,56 +359,124 @@ fn parse_statements(pairs: pest::iterators::Pairs<Rule>) -> Vec<Statement> {
 
 fn parse_node(pair: Pair<Rule>) -> CommandNode {
     let mut inner = pair.into_inner();
-    
+
     let mut current = inner.next().unwrap();
-    if current.as_rule() == Rule::doc_comment {
+    let doc = if current.as_rule() == Rule::doc_comment {
+        let text = current.as_str().trim_start_matches("///").trim().to_string();
         current = inner.next().unwrap();
-    }
-    
+        Some(text)
+    } else {
+        None
+    };
+
     let name = current.as_str().to_string();
-    
+
     current = inner.next().unwrap();
-    
+
     let params = if current.as_rule() == Rule::param_list {
-        let p: Vec<String> = current.into_inner()
-            .map(|p| p.as_str().to_string())
+        let p: Vec<Param> = current.into_inner()
+            .map(parse_param)
             .collect();
         current = inner.next().unwrap();
         p
     } else {
         Vec::new()
     };
-    
+
     let statements = parse_statements(current.into_inner());
-    
-    CommandNode { name, params, statements }
+
+    CommandNode { name, doc, params, statements }
 }
 
-fn parse_program(input: &str) -> Vec<CommandNode> {
-    let mut pairs = DSLParser::parse(Rule::program, input)
-        .expect("parse error");
-    
+fn parse_param(pair: Pair<Rule>) -> Param {
+    let mut inner = pair.into_inner();
+    let name = inner.next().unwrap().as_str().to_string();
+    let doc = inner.next().map(|d| d.as_str().trim_matches('"').to_string());
+    Param { name, doc }
+}
+
+fn parse_program(input: &str) -> Result<Vec<CommandNode>, CommanderError> {
+    let mut pairs = DSLParser::parse(Rule::program, input)?;
+
     let program = pairs.next().unwrap();
-    
-    program.into_inner()
+
+    Ok(program.into_inner()
         .filter(|p| p.as_rule() == Rule::node)
         .map(|p| parse_node(p))
+        .collect())
+}
+
+/// Direct `depends` edges declared at the top level of a node's body.
+fn direct_deps(node: &CommandNode) -> Vec<String> {
+    node.statements.iter()
+        .filter_map(|stmt| match stmt {
+            Statement::Depends(deps) => Some(deps.clone()),
+            _ => None,
+        })
+        .flatten()
         .collect()
 }
 
+/// DFS over the `depends` graph with visited/in-progress coloring, producing a
+/// topological order (prerequisites before the target) in `order`. Returns the
+/// cycle path (e.g. "a -> b -> a") if a back-edge is found.
+fn topo_sort(
+    name: &str,
+    all_nodes: &HashMap<String, &CommandNode>,
+    in_progress: &mut HashSet<String>,
+    visited: &mut HashSet<String>,
+    chain: &mut Vec<String>,
+    order: &mut Vec<String>,
+) -> Result<(), String> {
+    if visited.contains(name) {
+        return Ok(());
+    }
+    if in_progress.contains(name) {
+        chain.push(name.to_string());
+        return Err(chain.join(" -> "));
+    }
+
+    let Some(node) = all_nodes.get(name) else {
+        // Unknown dependency: reported by the caller, nothing to order.
+        return Ok(());
+    };
+
+    in_progress.insert(name.to_string());
+    chain.push(name.to_string());
+
+    for dep in direct_deps(node) {
+        topo_sort(&dep, all_nodes, in_progress, visited, chain, order)?;
+    }
+
+    chain.pop();
+    in_progress.remove(name);
+    visited.insert(name.to_string());
+    order.push(name.to_string());
+    Ok(())
+}
+
+/// Bundles the state shared across the whole `execute` recursion — the node
+/// table plus the two build-tracking sets — so `execute`/`execute_statements_only`
+/// take one parameter for it instead of three.
+struct ExecState<'a> {
+    all_nodes: &'a HashMap<String, &'a CommandNode>,
+    built: &'a mut HashSet<String>,
+    in_progress: &'a mut HashSet<String>,
+}
+
 fn execute_statements_only(
-    statements: &[Statement], 
-    path: &mut Vec<String>, 
-    env: &mut Environment, 
-    all_nodes: &HashMap<String, &CommandNode>
-) {
+    statements: &[Statement],
+    path: &mut Vec<String>,
+    env: &mut Environment,
+    state: &mut ExecState,
+) -> Result<(), CommanderError> {
     for stmt in statements {
         match stmt {
             Statement::Command(_) => {
                 // Ignora sub-comandos
             }
             Statement::Exec(cmd) => {
-                let expanded = env.expand(cmd);
+                let expanded = env.expand(cmd)?;
                 println!("\x1b[36m[exec]\x1b[0m {}", expanded.trim());
                 
                 let output = Command::new("sh")
@@ -236,36 +503,133 @@ fn execute_statements_only(
                     }
                 }
             }
-            Statement::Assignment(name, value) => {
-                let expanded = env.expand(value);
-                env.set(name.clone(), expanded.clone());
-                println!("\x1b[33m[set]\x1b[0m {} = {}", name, expanded);
+            Statement::Assignment(name, op, value) => {
+                // `?=` só expande `value` quando vai realmente ser usado —
+                // se `name` já estiver setado, o RHS nem chega a ser
+                // resolvido (evita abortar em modo strict por causa de um
+                // default que nunca ia ser aplicado).
+                if matches!(op, AssignOp::Default) && env.get(name).is_some() {
+                    continue;
+                }
+
+                let expanded = env.expand(value)?;
+                let resolved = match op {
+                    AssignOp::Set | AssignOp::Default => expanded,
+                    AssignOp::Append => {
+                        let existing = env.get(name).cloned().unwrap_or_default();
+                        format!("{}{}", existing, expanded)
+                    }
+                };
+                env.set(name.clone(), resolved.clone());
+                println!("\x1b[33m[set]\x1b[0m {} = {}", name, resolved);
+            }
+            Statement::Capture(name, op, cmd) => {
+                // `?=` só dispara o comando se `name` ainda não estiver
+                // setado; caindo fora daqui o valor existente é preservado
+                // sem rodar o shell.
+                if matches!(op, AssignOp::Default) && env.get(name).is_some() {
+                    continue;
+                }
+
+                let expanded = env.expand(cmd)?;
+                println!("\x1b[36m[capture]\x1b[0m {}", expanded.trim());
+
+                let output = Command::new("sh")
+                    .arg("-c")
+                    .arg(&expanded)
+                    .output();
+
+                match output {
+                    Ok(result) => {
+                        let captured = String::from_utf8_lossy(&result.stdout)
+                            .trim_end_matches('\n')
+                            .to_string();
+
+                        if !result.stderr.is_empty() {
+                            eprint!("{}", String::from_utf8_lossy(&result.stderr));
+                        }
+
+                        let stdout = match op {
+                            AssignOp::Append => {
+                                let existing = env.get(name).cloned().unwrap_or_default();
+                                format!("{}{}", existing, captured)
+                            }
+                            AssignOp::Set | AssignOp::Default => captured,
+                        };
+
+                        env.set(name.clone(), stdout.clone());
+                        env.set(format!("{}.code", name), result.status.code().unwrap_or(-1).to_string());
+                        println!("\x1b[33m[set]\x1b[0m {} = {}", name, stdout);
+                    }
+                    Err(e) => {
+                        eprintln!("\x1b[31m[error]\x1b[0m Failed to execute command: {}", e);
+                        env.set(name.clone(), String::new());
+                        env.set(format!("{}.code", name), "-1".to_string());
+                    }
+                }
             }
             Statement::Depends(deps) => {
                 for dep in deps {
-                    if let Some(dep_node) = all_nodes.get(dep) {
-                        println!("\x1b[35m[depends]\x1b[0m {}", dep);
+                    if !state.all_nodes.contains_key(dep) {
+                        return Err(CommanderError::UnknownDependency(Diagnostic {
+                            message: format!("Unknown dependency '{}'", dep),
+                            span: None,
+                        }));
+                    }
+
+                    // Seed from the live call stack (not just finished
+                    // targets) so a dependency chain that loops back to a
+                    // node that is still running is caught as a cycle. The
+                    // chain itself is seeded with the node whose `depends`
+                    // statement we're processing, so a reported path starts
+                    // at that node (e.g. "a -> a" or "a -> b -> a") instead
+                    // of at the first dependency; when the loop closes on an
+                    // ancestor further up the call stack, the path shows only
+                    // the part from this node onward.
+                    let mut in_progress_seed = state.in_progress.clone();
+                    let mut visited = state.built.clone();
+                    let mut chain = vec![path.last().cloned().unwrap_or_default()];
+                    let mut order = Vec::new();
+
+                    if let Err(cycle) = topo_sort(
+                        dep,
+                        state.all_nodes,
+                        &mut in_progress_seed,
+                        &mut visited,
+                        &mut chain,
+                        &mut order,
+                    ) {
+                        return Err(CommanderError::Cycle(Diagnostic {
+                            message: format!("Dependency cycle detected: {}", cycle),
+                            span: None,
+                        }));
+                    }
+
+                    for target in order {
+                        println!("\x1b[35m[depends]\x1b[0m {}", target);
+                        let dep_node = state.all_nodes[&target];
                         let mut dep_path = Vec::new();
-                        execute(dep_node, &mut dep_path, env, all_nodes, &[], None);
+                        execute(dep_node, &mut dep_path, env, &Flags::new(), None, state)?;
                     }
                 }
             }
             Statement::If { condition, then_block, else_block } => {
-                if env.eval_condition(condition) {
-                    execute_statements_only(then_block, path, env, all_nodes);
+                if env.eval_condition(condition)? {
+                    execute_statements_only(then_block, path, env, state)?;
                 } else if let Some(else_stmts) = else_block {
-                    execute_statements_only(else_stmts, path, env, all_nodes);
+                    execute_statements_only(else_stmts, path, env, state)?;
                 }
             }
             Statement::For { var, items, block } => {
                 for item in items {
-                    let expanded = env.expand(item);
+                    let expanded = env.expand(item)?;
                     env.set(var.clone(), expanded);
-                    execute_statements_only(block, path, env, all_nodes);
+                    execute_statements_only(block, path, env, state)?;
                 }
             }
         }
     }
+    Ok(())
 }
 
 fn find_subnode<'a>(node: &'a CommandNode, name: &str) -> Option<&'a CommandNode> {
@@ -280,35 +644,64 @@ fn find_subnode<'a>(node: &'a CommandNode, name: &str) -> Option<&'a CommandNode
 }
 
 fn execute(
-    node: &CommandNode, 
-    path: &mut Vec<String>, 
-    env: &mut Environment, 
-    all_nodes: &HashMap<String, &CommandNode>,
-    args: &[String],
-    subpath: Option<&[String]>
-) {
+    node: &CommandNode,
+    path: &mut Vec<String>,
+    env: &mut Environment,
+    args: &Flags,
+    subpath: Option<&[String]>,
+    state: &mut ExecState,
+) -> Result<(), CommanderError> {
     path.push(node.name.clone());
-    
-    // Define parâmetros
-    for (i, param) in node.params.iter().enumerate() {
-        if let Some(arg) = args.get(i) {
-            env.set(param.clone(), arg.clone());
-            println!("\x1b[32m[param]\x1b[0m {} = {}", param, arg);
+    // Marca o nó como "em execução" (não "concluído") até que todos os seus
+    // statements e sub-comandos tenham terminado. `built` só recebe o nome
+    // no fim desta função, para que um ciclo de `depends` que volte a um nó
+    // ainda em andamento seja detectado em vez de silenciosamente ignorado.
+    state.in_progress.insert(node.name.clone());
+
+    // Define parâmetros a partir das flags nomeadas, caindo para um valor já
+    // presente no Environment compartilhado (ex: setado por um --flag do nó
+    // raiz, ou por uma execução anterior) quando esta chamada não traz um.
+    let mut missing = Vec::new();
+    for param in &node.params {
+        match args.get(&param.name) {
+            Some(value) => {
+                env.set(param.name.clone(), value.clone());
+                println!("\x1b[32m[param]\x1b[0m {} = {}", param.name, value);
+            }
+            None if env.get(&param.name).is_some() => {}
+            None => missing.push(param.name.clone()),
         }
     }
 
+    if !missing.is_empty() {
+        path.pop();
+        state.in_progress.remove(&node.name);
+        return Err(CommanderError::MissingFlags(Diagnostic {
+            message: format!(
+                "Missing required flag(s) for '{}': {}",
+                node.name,
+                missing.iter().map(|p| format!("--{}", p)).collect::<Vec<_>>().join(", ")
+            ),
+            span: None,
+        }));
+    }
+
     // MUDANÇA: SEMPRE executa os statements do nó atual (exceto sub-comandos)
     // Isso garante que variáveis, depends, etc sejam processados
-    execute_statements_only(&node.statements, path, env, all_nodes);
+    execute_statements_only(&node.statements, path, env, state)?;
 
     // Se há um subpath, navega até o sub-nó
     if let Some(sub) = subpath {
         if !sub.is_empty() {
             if let Some(child) = find_subnode(node, &sub[0]) {
-                // Recursivamente executa o filho com o resto do subpath
-                execute(child, path, env, all_nodes, &[], Some(&sub[1..]));
+                // Recursivamente executa o filho com o resto do subpath.
+                execute(child, path, env, args, Some(&sub[1..]), state)?;
             } else {
-                eprintln!("\x1b[31m[error]\x1b[0m Subcommand '{}' not found in '{}'", sub[0], node.name);
+                let err = CommanderError::UnknownCommand(Diagnostic {
+                    message: format!("Subcommand '{}' not found in '{}'", sub[0], node.name),
+                    span: None,
+                });
+                eprintln!("{}", err);
             }
         }
         // Se subpath está vazio, já executamos os statements acima
@@ -316,12 +709,15 @@ fn execute(
         // Sem subpath, executa todos os sub-comandos também
         for stmt in &node.statements {
             if let Statement::Command(child) = stmt {
-                execute(child, path, env, all_nodes, &[], None);
+                execute(child, path, env, &Flags::new(), None, state)?;
             }
         }
     }
 
+    state.in_progress.remove(&node.name);
+    state.built.insert(node.name.clone());
     path.pop();
+    Ok(())
 }
 
 fn find_node<'a>(
@@ -331,56 +727,435 @@ fn find_node<'a>(
     nodes.iter().find(|n| n.name == name)
 }
 
+/// Walks a dotted command path (`cmd_path`) down from the root nodes, the same
+/// way `execute` would, without running anything. Used to resolve `--help`.
+fn resolve_node_path<'a>(
+    nodes: &'a [CommandNode],
+    cmd_path: &[String],
+) -> Option<&'a CommandNode> {
+    let mut node = find_node(nodes, cmd_path.first()?)?;
+    for name in &cmd_path[1..] {
+        node = find_subnode(node, name)?;
+    }
+    Some(node)
+}
+
+fn print_help(node: &CommandNode) {
+    println!("{}", node.name);
+    if let Some(doc) = &node.doc {
+        println!("    {}", doc);
+    }
+
+    if !node.params.is_empty() {
+        println!("\nParameters:");
+        for param in &node.params {
+            match &param.doc {
+                Some(doc) => println!("  --{}  {}", param.name, doc),
+                None => println!("  --{}", param.name),
+            }
+        }
+    }
+}
+
+/// Splits raw CLI args into a positional command path and named flags,
+/// recognizing `--name value`, `--name=value` and boolean `--name` forms.
+fn parse_args(args: &[String]) -> (Vec<String>, Flags, bool, bool) {
+    let mut cmd_path = Vec::new();
+    let mut flags = Flags::new();
+    let mut help = false;
+    let mut strict = false;
+
+    let mut i = 0;
+    while i < args.len() {
+        let arg = &args[i];
+
+        if arg == "--help" || arg == "-h" {
+            help = true;
+        } else if arg == "--strict" {
+            strict = true;
+        } else if let Some(flag) = arg.strip_prefix("--") {
+            if let Some((name, value)) = flag.split_once('=') {
+                flags.insert(name.to_string(), value.to_string());
+            } else {
+                match args.get(i + 1) {
+                    Some(next) if !next.starts_with("--") => {
+                        flags.insert(flag.to_string(), next.clone());
+                        i += 1;
+                    }
+                    _ => {
+                        flags.insert(flag.to_string(), "true".to_string());
+                    }
+                }
+            }
+        } else {
+            cmd_path.push(arg.clone());
+        }
+
+        i += 1;
+    }
+
+    (cmd_path, flags, help, strict)
+}
+
+fn unknown_command_error(name: &str) -> CommanderError {
+    CommanderError::UnknownCommand(Diagnostic {
+        message: format!("Command '{}' not found", name),
+        span: None,
+    })
+}
+
 fn main() {
+    if let Err(err) = run() {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), CommanderError> {
     let input = std::fs::read_to_string("Make.cmd")
-        .expect("Não foi possível ler o arquivo");
+        .map_err(|e| CommanderError::Io(format!("Não foi possível ler o arquivo: {}", e)))?;
 
-    let nodes = parse_program(&input);
+    let nodes = parse_program(&input)?;
     let args: Vec<String> = std::env::args().skip(1).collect();
 
-    if args.len() == 0 {
+    if args.is_empty() {
         eprintln!("\x1b[31m[error]\x1b[0m No command specified");
-        return
+        return Ok(());
     }
 
     let all_nodes: HashMap<String, &CommandNode> = nodes.iter()
         .map(|n| (n.name.clone(), n))
         .collect();
 
+    let (cmd_path, cmd_args, help_requested, strict) = parse_args(&args);
+
     let mut path = Vec::new();
-    let mut env = Environment::new();
+    let mut env = Environment::with_strict(strict);
+    let mut built = HashSet::new();
+    let mut in_progress = HashSet::new();
+    let mut state = ExecState {
+        all_nodes: &all_nodes,
+        built: &mut built,
+        in_progress: &mut in_progress,
+    };
 
     if args.is_empty() {
         let start = nodes.first().expect("nenhum nó encontrado");
-        execute(start, &mut path, &mut env, &all_nodes, &[], None);
+        execute(start, &mut path, &mut env, &Flags::new(), None, &mut state)?;
     } else {
-        let mut cmd_path = vec![];
-        let mut cmd_args = vec![];
-
-        for arg in &args {
-            if arg.starts_with("--") {
-                cmd_args.push(arg.trim_start_matches("--").to_string());
-            } else {
-                cmd_path.push(arg.clone());
-            }
-        }
-
         if cmd_path.is_empty() {
             eprintln!("\x1b[31m[error]\x1b[0m No command specified");
-            return;
+            return Ok(());
+        }
+
+        if help_requested {
+            let node = resolve_node_path(&nodes, &cmd_path)
+                .ok_or_else(|| unknown_command_error(&cmd_path.join(" ")))?;
+            print_help(node);
+            return Ok(());
         }
 
         let root_name = &cmd_path[0];
         let subpath = &cmd_path[1..];
 
-        if let Some(root_node) = find_node(&nodes, root_name) {
-            if subpath.is_empty() {
-                execute(root_node, &mut path, &mut env, &all_nodes, &cmd_args, None);
-            } else {
-                execute(root_node, &mut path, &mut env, &all_nodes, &cmd_args, Some(subpath));
-            }
+        let root_node = find_node(&nodes, root_name)
+            .ok_or_else(|| unknown_command_error(root_name))?;
+
+        if subpath.is_empty() {
+            execute(root_node, &mut path, &mut env, &cmd_args, None, &mut state)?;
         } else {
-            eprintln!("\x1b[31m[error]\x1b[0m Command '{}' not found", root_name);
+            execute(root_node, &mut path, &mut env, &cmd_args, Some(subpath), &mut state)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(name: &str, depends: &[&str]) -> CommandNode {
+        let mut statements = Vec::new();
+        if !depends.is_empty() {
+            statements.push(Statement::Depends(depends.iter().map(|d| d.to_string()).collect()));
         }
+        CommandNode { name: name.to_string(), doc: None, params: Vec::new(), statements }
+    }
+
+    #[test]
+    fn topo_sort_orders_prerequisites_before_target() {
+        let build = node("build", &[]);
+        let test = node("test", &["build"]);
+        let all_nodes: HashMap<String, &CommandNode> =
+            [(build.name.clone(), &build), (test.name.clone(), &test)].into_iter().collect();
+
+        let mut in_progress = HashSet::new();
+        let mut visited = HashSet::new();
+        let mut chain = Vec::new();
+        let mut order = Vec::new();
+
+        topo_sort("test", &all_nodes, &mut in_progress, &mut visited, &mut chain, &mut order).unwrap();
+
+        assert_eq!(order, vec!["build".to_string(), "test".to_string()]);
+    }
+
+    #[test]
+    fn topo_sort_detects_a_cycle() {
+        let a = node("a", &["b"]);
+        let b = node("b", &["a"]);
+        let all_nodes: HashMap<String, &CommandNode> =
+            [(a.name.clone(), &a), (b.name.clone(), &b)].into_iter().collect();
+
+        let mut in_progress = HashSet::new();
+        let mut visited = HashSet::new();
+        let mut chain = Vec::new();
+        let mut order = Vec::new();
+
+        let result = topo_sort("a", &all_nodes, &mut in_progress, &mut visited, &mut chain, &mut order);
+
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("a"));
+    }
+
+    #[test]
+    fn topo_sort_skips_nodes_already_built() {
+        // A dependency already in `visited` (seeded from `built`) is treated
+        // as satisfied and does not reappear in `order`.
+        let build = node("build", &[]);
+        let test = node("test", &["build"]);
+        let all_nodes: HashMap<String, &CommandNode> =
+            [(build.name.clone(), &build), (test.name.clone(), &test)].into_iter().collect();
+
+        let mut in_progress = HashSet::new();
+        let mut visited: HashSet<String> = ["build".to_string()].into_iter().collect();
+        let mut chain = Vec::new();
+        let mut order = Vec::new();
+
+        topo_sort("test", &all_nodes, &mut in_progress, &mut visited, &mut chain, &mut order).unwrap();
+
+        assert_eq!(order, vec!["test".to_string()]);
+    }
+
+    #[test]
+    fn execute_falls_back_to_environment_value_for_missing_param() {
+        let build = CommandNode {
+            name: "build".to_string(),
+            doc: None,
+            params: vec![Param { name: "target".to_string(), doc: None }],
+            statements: vec![Statement::Assignment(
+                "ran".to_string(),
+                AssignOp::Set,
+                "yes".to_string(),
+            )],
+        };
+        let all_nodes: HashMap<String, &CommandNode> = [(build.name.clone(), &build)].into_iter().collect();
+        let mut env = Environment::with_strict(false);
+        env.set("target".to_string(), "prod".to_string());
+
+        let mut path = Vec::new();
+        let mut built = HashSet::new();
+        let mut in_progress = HashSet::new();
+        let mut state = ExecState { all_nodes: &all_nodes, built: &mut built, in_progress: &mut in_progress };
+
+        execute(&build, &mut path, &mut env, &Flags::new(), None, &mut state).unwrap();
+
+        assert_eq!(env.get("ran"), Some(&"yes".to_string()));
+        assert!(state.built.contains("build"));
+    }
+
+    #[test]
+    fn execute_reports_missing_param_with_no_fallback() {
+        let build = CommandNode {
+            name: "build".to_string(),
+            doc: None,
+            params: vec![Param { name: "target".to_string(), doc: None }],
+            statements: Vec::new(),
+        };
+        let all_nodes: HashMap<String, &CommandNode> = [(build.name.clone(), &build)].into_iter().collect();
+        let mut env = Environment::with_strict(false);
+
+        let mut path = Vec::new();
+        let mut built = HashSet::new();
+        let mut in_progress = HashSet::new();
+        let mut state = ExecState { all_nodes: &all_nodes, built: &mut built, in_progress: &mut in_progress };
+
+        let result = execute(&build, &mut path, &mut env, &Flags::new(), None, &mut state);
+
+        // A required flag with nothing to fall back to is a hard error: the
+        // node never runs its statements and is never marked as built.
+        assert!(matches!(result, Err(CommanderError::MissingFlags(_))));
+        assert!(!state.built.contains("build"));
+    }
+
+    #[test]
+    fn expand_substitutes_bare_and_braced_vars() {
+        let mut env = Environment::with_strict(false);
+        env.set("name".to_string(), "world".to_string());
+
+        assert_eq!(env.expand("hello $name").unwrap(), "hello world");
+        assert_eq!(env.expand("hello ${name}").unwrap(), "hello world");
+        assert_eq!(env.expand("literal $$name").unwrap(), "literal $name");
+    }
+
+    #[test]
+    fn expand_uses_default_fallback_for_unset_var() {
+        let env = Environment::with_strict(false);
+        assert_eq!(env.expand("${missing:-fallback}").unwrap(), "fallback");
+    }
+
+    #[test]
+    fn expand_returns_empty_string_for_unset_var_when_not_strict() {
+        let env = Environment::with_strict(false);
+        assert_eq!(env.expand("$missing").unwrap(), "");
+    }
+
+    #[test]
+    fn expand_errors_on_unset_var_in_strict_mode() {
+        let env = Environment::with_strict(true);
+        assert!(env.expand("$missing").is_err());
+    }
+
+    #[test]
+    fn assignment_default_only_applies_when_unset() {
+        let mut env = Environment::with_strict(false);
+        env.set("x".to_string(), "existing".to_string());
+
+        let all_nodes: HashMap<String, &CommandNode> = HashMap::new();
+        let mut built = HashSet::new();
+        let mut in_progress = HashSet::new();
+        let mut state = ExecState { all_nodes: &all_nodes, built: &mut built, in_progress: &mut in_progress };
+        let mut path = Vec::new();
+
+        let statements = vec![
+            Statement::Assignment("x".to_string(), AssignOp::Default, "new".to_string()),
+            Statement::Assignment("y".to_string(), AssignOp::Default, "fallback".to_string()),
+        ];
+
+        execute_statements_only(&statements, &mut path, &mut env, &mut state).unwrap();
+
+        assert_eq!(env.get("x"), Some(&"existing".to_string()));
+        assert_eq!(env.get("y"), Some(&"fallback".to_string()));
+    }
+
+    #[test]
+    fn assignment_append_concatenates_onto_existing_value() {
+        let mut env = Environment::with_strict(false);
+        env.set("greeting".to_string(), "a".to_string());
+
+        let all_nodes: HashMap<String, &CommandNode> = HashMap::new();
+        let mut built = HashSet::new();
+        let mut in_progress = HashSet::new();
+        let mut state = ExecState { all_nodes: &all_nodes, built: &mut built, in_progress: &mut in_progress };
+        let mut path = Vec::new();
+
+        let statements = vec![Statement::Assignment("greeting".to_string(), AssignOp::Append, "b".to_string())];
+
+        execute_statements_only(&statements, &mut path, &mut env, &mut state).unwrap();
+
+        assert_eq!(env.get("greeting"), Some(&"ab".to_string()));
+    }
+
+    #[test]
+    fn assignment_default_skips_rhs_expansion_when_already_set() {
+        // In strict mode, expanding a reference to an unknown variable is a
+        // hard error — `?=` must not even attempt it when the default won't
+        // apply, so this must succeed despite `$missing` being undefined.
+        let mut env = Environment::with_strict(true);
+        env.set("x".to_string(), "existing".to_string());
+
+        let all_nodes: HashMap<String, &CommandNode> = HashMap::new();
+        let mut built = HashSet::new();
+        let mut in_progress = HashSet::new();
+        let mut state = ExecState { all_nodes: &all_nodes, built: &mut built, in_progress: &mut in_progress };
+        let mut path = Vec::new();
+
+        let statements = vec![Statement::Assignment("x".to_string(), AssignOp::Default, "$missing".to_string())];
+
+        execute_statements_only(&statements, &mut path, &mut env, &mut state).unwrap();
+
+        assert_eq!(env.get("x"), Some(&"existing".to_string()));
+    }
+
+    #[test]
+    fn capture_stores_trimmed_stdout_and_exit_code() {
+        let mut env = Environment::with_strict(false);
+        let all_nodes: HashMap<String, &CommandNode> = HashMap::new();
+        let mut built = HashSet::new();
+        let mut in_progress = HashSet::new();
+        let mut state = ExecState { all_nodes: &all_nodes, built: &mut built, in_progress: &mut in_progress };
+        let mut path = Vec::new();
+
+        let statements = vec![Statement::Capture("out".to_string(), AssignOp::Set, "echo hi".to_string())];
+
+        execute_statements_only(&statements, &mut path, &mut env, &mut state).unwrap();
+
+        assert_eq!(env.get("out"), Some(&"hi".to_string()));
+        assert_eq!(env.get("out.code"), Some(&"0".to_string()));
+    }
+
+    #[test]
+    fn capture_append_concatenates_and_default_skips_when_set() {
+        let mut env = Environment::with_strict(false);
+        env.set("out".to_string(), "a".to_string());
+
+        let all_nodes: HashMap<String, &CommandNode> = HashMap::new();
+        let mut built = HashSet::new();
+        let mut in_progress = HashSet::new();
+        let mut state = ExecState { all_nodes: &all_nodes, built: &mut built, in_progress: &mut in_progress };
+        let mut path = Vec::new();
+
+        let statements = vec![
+            // `?=` must not run the shell at all since `out` is already set.
+            Statement::Capture("out".to_string(), AssignOp::Default, "echo should-not-run".to_string()),
+            Statement::Capture("out".to_string(), AssignOp::Append, "echo b".to_string()),
+        ];
+
+        execute_statements_only(&statements, &mut path, &mut env, &mut state).unwrap();
+
+        assert_eq!(env.get("out"), Some(&"ab".to_string()));
+    }
+
+    #[test]
+    fn capture_records_nonzero_exit_code() {
+        let mut env = Environment::with_strict(false);
+        let all_nodes: HashMap<String, &CommandNode> = HashMap::new();
+        let mut built = HashSet::new();
+        let mut in_progress = HashSet::new();
+        let mut state = ExecState { all_nodes: &all_nodes, built: &mut built, in_progress: &mut in_progress };
+        let mut path = Vec::new();
+
+        let statements = vec![Statement::Capture("r".to_string(), AssignOp::Set, "exit 3".to_string())];
+
+        execute_statements_only(&statements, &mut path, &mut env, &mut state).unwrap();
+
+        assert_eq!(env.get("r.code"), Some(&"3".to_string()));
+    }
+
+    #[test]
+    fn diagnostic_display_renders_caret_at_span() {
+        let diag = Diagnostic {
+            message: "Unexpected token".to_string(),
+            span: Some(SourceSpan { line: 2, col: 5, line_text: "  bad(".to_string() }),
+        };
+
+        let rendered = diag.to_string();
+
+        assert!(rendered.contains("Unexpected token"));
+        assert!(rendered.contains("line 2:5"));
+        assert!(rendered.contains("bad("));
+        assert!(rendered.ends_with("   |     ^"));
+    }
+
+    #[test]
+    fn diagnostic_display_without_span_omits_location() {
+        let diag = Diagnostic { message: "Unknown variable 'x'".to_string(), span: None };
+        assert_eq!(diag.to_string(), "\x1b[31m[error]\x1b[0m Unknown variable 'x'\n");
+    }
+
+    #[test]
+    fn parse_program_reports_malformed_input_as_parse_error() {
+        let result = parse_program("not valid commander syntax {{{");
+        assert!(matches!(result, Err(CommanderError::Parse(_))));
     }
 }
\ No newline at end of file